@@ -1,8 +1,13 @@
 use crate::{arg::value, output::Light as OutputLight, output::Scan as OutputScan, util};
 use huelib::resource::{light, Modifier};
 use huelib::Color;
+use serde::Deserialize;
+use std::{convert::TryFrom, str::FromStr, thread, time::Duration};
 use structopt::StructOpt;
 
+/// Interval at which intermediate steps of a flow are sent to the bridge.
+const FLOW_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Debug, StructOpt)]
 pub enum Arg {
     /// Modifies the state and attributes of a light
@@ -58,6 +63,282 @@ pub struct Set {
     /// Renames the light
     #[structopt(long, short)]
     name: Option<String>,
+    /// Adds a waypoint to a flow effect, repeat to add more steps. Format:
+    /// `<duration_ms>[,hex=<RRGGBB>|,xy=<x>:<y>][,brightness=<pct>][,color-temperature=<mired>]`
+    #[structopt(long)]
+    flow: Vec<FlowStep>,
+    /// Repeats the flow waypoints this many times, 0 repeats forever
+    #[structopt(long = "loop", default_value = "1")]
+    loop_count: u32,
+}
+
+/// JSON payload accepted by `huectl mqtt` on `<base_topic>/light/<id>/set`. Unlike `Set`, every
+/// field is a plain, `serde`-friendly type rather than the CLI-only `value::*` wrappers, since
+/// those only implement `FromStr` for argument parsing, not `Deserialize`.
+#[derive(Debug, Deserialize)]
+pub struct LightCommand {
+    #[serde(default)]
+    on: Option<bool>,
+    #[serde(default)]
+    brightness: Option<String>,
+    #[serde(default)]
+    hue: Option<String>,
+    #[serde(default)]
+    saturation: Option<String>,
+    #[serde(default)]
+    color_temperature: Option<String>,
+    #[serde(default)]
+    color_space_coordinates: Option<[f32; 2]>,
+    #[serde(default)]
+    color_rgb: Option<[u8; 3]>,
+    #[serde(default)]
+    color_hex: Option<String>,
+    #[serde(default)]
+    alert: Option<String>,
+    #[serde(default)]
+    effect: Option<String>,
+    #[serde(default)]
+    transition_time: Option<u16>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+impl TryFrom<LightCommand> for Set {
+    type Error = crate::arg::ParseError;
+
+    fn try_from(command: LightCommand) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: String::new(),
+            on: command.on.unwrap_or(false),
+            off: command.on == Some(false),
+            brightness: command.brightness.map(|v| v.parse()).transpose()?,
+            hue: command.hue.map(|v| v.parse()).transpose()?,
+            saturation: command.saturation.map(|v| v.parse()).transpose()?,
+            color_temperature: command.color_temperature.map(|v| v.parse()).transpose()?,
+            color_space_coordinates: command.color_space_coordinates.map(|v| v.to_vec()),
+            color_rgb: command.color_rgb.map(|v| v.to_vec()),
+            color_hex: command.color_hex.map(|v| v.parse()).transpose()?,
+            alert: command.alert.map(|v| v.parse()).transpose()?,
+            effect: command.effect.map(|v| v.parse()).transpose()?,
+            transition_time: command.transition_time,
+            name: command.name,
+            flow: Vec::new(),
+            loop_count: 1,
+        })
+    }
+}
+
+/// A single waypoint of a `--flow` effect.
+#[derive(Debug, Clone)]
+struct FlowStep {
+    color: Option<FlowColor>,
+    brightness: Option<u8>,
+    color_temperature: Option<u16>,
+    hue: Option<u16>,
+    saturation: Option<u8>,
+    duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FlowColor {
+    Rgb(u8, u8, u8),
+    Xy(f32, f32),
+}
+
+impl FromStr for FlowStep {
+    type Err = crate::arg::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || crate::arg::ParseError::new("Invalid flow waypoint");
+        let mut parts = s.split(',');
+        let duration_ms: u64 = parts.next().ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+        let mut step = Self {
+            color: None,
+            brightness: None,
+            color_temperature: None,
+            hue: None,
+            saturation: None,
+            duration: Duration::from_millis(duration_ms),
+        };
+        for part in parts {
+            let (key, value) = {
+                let mut kv = part.splitn(2, '=');
+                (kv.next().ok_or_else(invalid)?, kv.next().ok_or_else(invalid)?)
+            };
+            match key {
+                "hex" => {
+                    let rgb = u32::from_str_radix(value, 16).map_err(|_| invalid())?;
+                    if value.len() != 6 {
+                        return Err(invalid());
+                    }
+                    let r = ((rgb >> 16) & 0xff) as u8;
+                    let g = ((rgb >> 8) & 0xff) as u8;
+                    let b = (rgb & 0xff) as u8;
+                    step.color = Some(FlowColor::Rgb(r, g, b));
+                }
+                "xy" => {
+                    let mut xy = value.splitn(2, ':');
+                    let x: f32 = xy.next().ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+                    let y: f32 = xy.next().ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+                    step.color = Some(FlowColor::Xy(x, y));
+                }
+                "brightness" => step.brightness = Some(value.parse().map_err(|_| invalid())?),
+                "color-temperature" => {
+                    step.color_temperature = Some(value.parse().map_err(|_| invalid())?)
+                }
+                "hue" => step.hue = Some(value.parse().map_err(|_| invalid())?),
+                "saturation" => step.saturation = Some(value.parse().map_err(|_| invalid())?),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(step)
+    }
+}
+
+impl FlowStep {
+    /// Interpolates between this waypoint and `other` at `t` (0.0 = self, 1.0 = other).
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let color = match (self.color, other.color) {
+            (Some(FlowColor::Rgb(r1, g1, b1)), Some(FlowColor::Rgb(r2, g2, b2))) => Some(
+                FlowColor::Rgb(lerp_u8(r1, r2, t), lerp_u8(g1, g2, t), lerp_u8(b1, b2, t)),
+            ),
+            (Some(FlowColor::Xy(x1, y1)), Some(FlowColor::Xy(x2, y2))) => {
+                Some(FlowColor::Xy(lerp_f32(x1, x2, t), lerp_f32(y1, y2, t)))
+            }
+            // Waypoints on either side of a color space change can't be interpolated in a
+            // shared space, so hold the previous color until the step completes.
+            (Some(from), Some(to)) => Some(if t < 1.0 { from } else { to }),
+            (Some(c), None) | (None, Some(c)) => Some(c),
+            (None, None) => None,
+        };
+        Self {
+            color,
+            brightness: lerp_option_u8(self.brightness, other.brightness, t),
+            color_temperature: lerp_option_u16(self.color_temperature, other.color_temperature, t),
+            hue: lerp_option_hue(self.hue, other.hue, t),
+            saturation: lerp_option_u8(self.saturation, other.saturation, t),
+            duration: FLOW_FRAME_INTERVAL,
+        }
+    }
+
+    fn to_state_modifier(&self, transition: Duration) -> light::StateModifier {
+        let mut modifier = light::StateModifier::new().on(true);
+        if let Some(color) = self.color {
+            modifier = modifier.color(match color {
+                FlowColor::Rgb(r, g, b) => Color::from_rgb(r, g, b),
+                FlowColor::Xy(x, y) => Color::from_space_coordinates(x, y),
+            });
+        }
+        if let Some(v) = self.brightness {
+            // Waypoint brightness is a percentage, the bridge expects a value between 0 and 254.
+            let v = (f32::from(v.min(100)) * 2.54).round() as u8;
+            modifier = modifier.brightness(huelib::resource::Adjust::Override, v);
+        }
+        if let Some(v) = self.hue {
+            modifier = modifier.hue(huelib::resource::Adjust::Override, v);
+        }
+        if let Some(v) = self.saturation {
+            modifier = modifier.saturation(huelib::resource::Adjust::Override, v);
+        }
+        if let Some(v) = self.color_temperature {
+            modifier = modifier.color_temperature(huelib::resource::Adjust::Override, v);
+        }
+        modifier.transition_time(duration_to_deciseconds(transition))
+    }
+}
+
+fn lerp_u8(a: u8, b: u8, t: f32) -> u8 {
+    (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8
+}
+
+fn lerp_f32(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_option_u8(a: Option<u8>, b: Option<u8>, t: f32) -> Option<u8> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(lerp_u8(a, b, t)),
+        (Some(v), None) | (None, Some(v)) => Some(v),
+        (None, None) => None,
+    }
+}
+
+fn lerp_option_u16(a: Option<u16>, b: Option<u16>, t: f32) -> Option<u16> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            Some((f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u16)
+        }
+        (Some(v), None) | (None, Some(v)) => Some(v),
+        (None, None) => None,
+    }
+}
+
+/// Interpolates a hue value (0-65535) along the shorter arc of the color wheel.
+fn lerp_option_hue(a: Option<u16>, b: Option<u16>, t: f32) -> Option<u16> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let diff = i32::from(b) - i32::from(a);
+            let shortest = if diff.abs() > 32768 {
+                if diff > 0 {
+                    diff - 65536
+                } else {
+                    diff + 65536
+                }
+            } else {
+                diff
+            };
+            let result = i32::from(a) + (shortest as f32 * t).round() as i32;
+            Some(result.rem_euclid(65536) as u16)
+        }
+        (Some(v), None) | (None, Some(v)) => Some(v),
+        (None, None) => None,
+    }
+}
+
+fn duration_to_deciseconds(duration: Duration) -> u16 {
+    (duration.as_millis() / 100)
+        .max(1)
+        .min(u16::MAX as u128) as u16
+}
+
+fn run_flow(bridge: &huelib::Bridge, id: &str, steps: &[FlowStep], loop_count: u32) {
+    let mut iteration = 0u32;
+    let mut previous: Option<&FlowStep> = None;
+    loop {
+        for step in steps {
+            match previous {
+                Some(prev) => animate_flow_step(bridge, id, prev, step),
+                None => apply_flow_step(bridge, id, step),
+            }
+            previous = Some(step);
+        }
+        iteration += 1;
+        if loop_count != 0 && iteration >= loop_count {
+            break;
+        }
+    }
+}
+
+fn apply_flow_step(bridge: &huelib::Bridge, id: &str, step: &FlowStep) {
+    let modifier = step.to_state_modifier(step.duration);
+    if let Err(e) = bridge.set_light_state(id, &modifier) {
+        eprintln!("Failed to apply flow waypoint for light '{}': {}", id, e);
+    }
+    thread::sleep(step.duration);
+}
+
+fn animate_flow_step(bridge: &huelib::Bridge, id: &str, from: &FlowStep, to: &FlowStep) {
+    let frame_count = (to.duration.as_millis() / FLOW_FRAME_INTERVAL.as_millis()).max(1);
+    for frame in 1..=frame_count {
+        let t = frame as f32 / frame_count as f32;
+        let modifier = from
+            .interpolate(to, t)
+            .to_state_modifier(FLOW_FRAME_INTERVAL);
+        if let Err(e) = bridge.set_light_state(id, &modifier) {
+            eprintln!("Failed to apply flow frame for light '{}': {}", id, e);
+        }
+        thread::sleep(FLOW_FRAME_INTERVAL);
+    }
 }
 
 impl Set {
@@ -112,6 +393,10 @@ impl Set {
 
 pub fn set(arg: Set) {
     let bridge = util::get_bridge();
+    if !arg.flow.is_empty() {
+        run_flow(&bridge, &arg.id, &arg.flow, arg.loop_count);
+        return;
+    }
     let mut responses = Vec::new();
     let state_modifier = arg.to_state_modifier();
     if !state_modifier.is_empty() {
@@ -197,3 +482,76 @@ pub fn delete(arg: Delete) {
         Err(e) => exit!("Failed to delete light", e),
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lerp_option_hue_wraps_backward_through_zero() {
+        // 1000 -> 65000 is shorter going backward through 0 than forward.
+        assert_eq!(lerp_option_hue(Some(1000), Some(65000), 0.5), Some(232));
+    }
+
+    #[test]
+    fn lerp_option_hue_wraps_forward_through_zero() {
+        // The reverse of the above should land on the same point on the wheel.
+        assert_eq!(lerp_option_hue(Some(65000), Some(1000), 0.5), Some(232));
+    }
+
+    #[test]
+    fn lerp_option_hue_without_wraparound() {
+        assert_eq!(lerp_option_hue(Some(1000), Some(2000), 0.5), Some(1500));
+    }
+
+    #[test]
+    fn lerp_option_hue_with_missing_value() {
+        assert_eq!(lerp_option_hue(Some(1000), None, 0.5), Some(1000));
+        assert_eq!(lerp_option_hue(None, Some(2000), 0.5), Some(2000));
+        assert_eq!(lerp_option_hue(None, None, 0.5), None);
+    }
+
+    #[test]
+    fn flow_step_from_str_parses_waypoint() {
+        let step: FlowStep = "2000,hex=ff8800,brightness=80".parse().unwrap();
+        assert_eq!(step.duration, Duration::from_millis(2000));
+        assert!(matches!(step.color, Some(FlowColor::Rgb(0xff, 0x88, 0x00))));
+        assert_eq!(step.brightness, Some(80));
+    }
+
+    #[test]
+    fn flow_step_from_str_parses_xy_color() {
+        let step: FlowStep = "500,xy=0.3:0.4".parse().unwrap();
+        assert!(matches!(step.color, Some(FlowColor::Xy(x, y)) if x == 0.3 && y == 0.4));
+    }
+
+    #[test]
+    fn flow_step_from_str_rejects_invalid_duration() {
+        assert!("not-a-number,brightness=80".parse::<FlowStep>().is_err());
+    }
+
+    #[test]
+    fn flow_step_from_str_rejects_unknown_key() {
+        assert!("2000,unknown=1".parse::<FlowStep>().is_err());
+    }
+
+    #[test]
+    fn duration_to_deciseconds_rounds_down_to_a_whole_decisecond() {
+        assert_eq!(duration_to_deciseconds(Duration::from_millis(250)), 2);
+    }
+
+    #[test]
+    fn duration_to_deciseconds_never_returns_zero() {
+        assert_eq!(duration_to_deciseconds(Duration::from_millis(0)), 1);
+    }
+
+    #[test]
+    fn duration_to_deciseconds_clamps_instead_of_truncating() {
+        // 6,600,000ms (110min) is 66,000 deciseconds, which overflows u16 (max 65,535) if cast
+        // directly instead of clamped, wrapping down to a ~46s transition.
+        assert_eq!(
+            duration_to_deciseconds(Duration::from_millis(6_600_000)),
+            u16::MAX
+        );
+    }
+}
@@ -1,21 +1,26 @@
 mod config;
 mod group;
 mod light;
+mod mqtt;
 mod resourcelink;
 mod rule;
 mod scene;
 mod schedule;
 mod sensor;
 mod value;
+mod watch;
 
-use std::{fmt, net::IpAddr};
+use std::{fmt, net::IpAddr, str::FromStr};
 use structopt::StructOpt;
 
 pub fn exec() {
     let args = Args::from_args();
+    crate::profile::select(args.profile);
     match args.subcommand {
-        Subcommand::Discover => discover(),
+        Subcommand::Discover(v) => discover(v),
         Subcommand::Register(v) => register(v),
+        Subcommand::Mqtt(v) => mqtt::mqtt(v),
+        Subcommand::Watch(v) => watch::watch(v),
         Subcommand::Config(v) => match v {
             config::Arg::Set(v) => config::set(v),
             config::Arg::Get => config::get(),
@@ -68,6 +73,9 @@ pub fn exec() {
 /// A command line interface to Philips Hue
 #[derive(Debug, StructOpt)]
 pub struct Args {
+    /// Named bridge profile to use from the config file, overridden by the environment variables
+    #[structopt(long, short, global = true)]
+    pub profile: Option<String>,
     #[structopt(subcommand)]
     pub subcommand: Subcommand,
 }
@@ -75,9 +83,13 @@ pub struct Args {
 #[derive(Debug, StructOpt)]
 pub enum Subcommand {
     /// Discovers bridges in the local network
-    Discover,
+    Discover(Discover),
     /// Registers a new user on a bridge
     Register(Register),
+    /// Runs as a long-lived daemon that mirrors bridge state over MQTT
+    Mqtt(mqtt::Mqtt),
+    /// Streams changes to lights, groups or sensors as they happen
+    Watch(watch::Watch),
     /// Modifies or prints the bridge configuration
     Config(config::Arg),
     /// Modifies, prints, searches or deletes lights
@@ -96,13 +108,95 @@ pub enum Subcommand {
     Sensor(sensor::Arg),
 }
 
-pub fn discover() {
-    let ip_addresses = match huelib::bridge::discover() {
+#[derive(Debug, StructOpt)]
+pub struct Discover {
+    /// Method used to discover bridges
+    #[structopt(
+        long,
+        short,
+        case_insensitive = true,
+        default_value = "mdns",
+        possible_values = DiscoveryMethod::variants()
+    )]
+    method: DiscoveryMethod,
+    /// Fetches and prints the model, serial number and API version of each bridge
+    #[cfg(feature = "upnp-description")]
+    #[structopt(long, short)]
+    describe: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiscoveryMethod {
+    Mdns,
+    Nupnp,
+    All,
+}
+
+impl DiscoveryMethod {
+    pub fn variants() -> &'static [&'static str] {
+        &["mdns", "nupnp", "all"]
+    }
+}
+
+impl FromStr for DiscoveryMethod {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mdns" => Ok(Self::Mdns),
+            "nupnp" => Ok(Self::Nupnp),
+            "all" => Ok(Self::All),
+            _ => Err(ParseError::new("Invalid discovery method")),
+        }
+    }
+}
+
+pub fn discover(arg: Discover) {
+    let mut ip_addresses = match arg.method {
+        DiscoveryMethod::Mdns => discover_mdns(),
+        DiscoveryMethod::Nupnp => discover_nupnp(),
+        DiscoveryMethod::All => {
+            let mut addresses = discover_mdns();
+            addresses.extend(discover_nupnp());
+            addresses
+        }
+    };
+    if arg.method == DiscoveryMethod::Mdns && ip_addresses.is_empty() {
+        eprintln!("No bridges found via mDNS, falling back to NUPnP...");
+        ip_addresses = discover_nupnp();
+    }
+    ip_addresses.sort();
+    ip_addresses.dedup();
+
+    for ip_address in ip_addresses {
+        #[cfg(feature = "upnp-description")]
+        if arg.describe {
+            match huelib::bridge::Description::get(ip_address) {
+                Ok(v) => println!(
+                    "{}\tmodel={}\tserial={}\tapi_version={}",
+                    ip_address, v.model_name, v.serial_number, v.api_version
+                ),
+                Err(e) => {
+                    eprintln!("Failed to get description of bridge '{}': {}", ip_address, e)
+                }
+            };
+            continue;
+        }
+        println!("{}", ip_address);
+    }
+}
+
+fn discover_mdns() -> Vec<IpAddr> {
+    match huelib::bridge::discover() {
         Ok(v) => v,
         Err(e) => exit!("Failed to discover bridges", e),
-    };
-    for i in ip_addresses {
-        println!("{}", i);
+    }
+}
+
+fn discover_nupnp() -> Vec<IpAddr> {
+    match huelib::bridge::discover_nupnp() {
+        Ok(v) => v,
+        Err(e) => exit!("Failed to discover bridges via NUPnP", e),
     }
 }
 
@@ -114,6 +208,9 @@ pub struct Register {
     /// Sets environment variables for the current session
     #[structopt(long, short)]
     pub set_env: bool,
+    /// Saves the registered credentials into a named bridge profile in the config file
+    #[structopt(long)]
+    pub save_profile: Option<String>,
 }
 
 pub fn register(arg: Register) {
@@ -137,10 +234,20 @@ pub fn register(arg: Register) {
             e
         ),
     };
+    if let Some(name) = &arg.save_profile {
+        crate::profile::save(
+            name,
+            crate::profile::Profile {
+                ip_address,
+                username: user.name.clone(),
+            },
+        );
+        println!("Saved bridge profile '{}'", name);
+    }
     if arg.set_env {
         std::env::set_var(crate::config::VAR_BRIDGE_IP, ip_address.to_string());
         std::env::set_var(crate::config::VAR_BRIDGE_USERNAME, user.name);
-    } else {
+    } else if arg.save_profile.is_none() {
         println!("{}={}", crate::config::VAR_BRIDGE_IP, ip_address);
         println!("{}={}", crate::config::VAR_BRIDGE_USERNAME, user.name);
     }
@@ -0,0 +1,186 @@
+use crate::arg::light;
+use crate::output::Light as OutputLight;
+use crate::util;
+use huelib::resource::Modifier;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Mqtt {
+    /// Hostname or IP address of the MQTT broker
+    #[structopt(long, default_value = "localhost")]
+    host: String,
+    /// Port of the MQTT broker
+    #[structopt(long, default_value = "1883")]
+    port: u16,
+    /// Topic prefix used for publishing and subscribing
+    #[structopt(long, default_value = "hue")]
+    base_topic: String,
+    /// Interval in seconds between polls of the bridge state
+    #[structopt(long, default_value = "5")]
+    poll_interval: u64,
+}
+
+pub fn mqtt(arg: Mqtt) {
+    spawn_poller(&arg);
+    loop {
+        if let Err(e) = run(&arg) {
+            eprintln!(
+                "Lost connection to the MQTT broker, reconnecting in 5 seconds: {}",
+                e
+            );
+            thread::sleep(Duration::from_secs(5));
+        }
+    }
+}
+
+fn run(arg: &Mqtt) -> Result<(), Box<dyn std::error::Error>> {
+    let mut options = MqttOptions::new("huectl-rs", &arg.host, arg.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (mut client, mut connection) = Client::new(options, 10);
+
+    let set_topic_filter = format!("{}/light/+/set", arg.base_topic);
+    client.subscribe(&set_topic_filter, QoS::AtLeastOnce)?;
+
+    for notification in connection.iter() {
+        if let Event::Incoming(Packet::Publish(publish)) = notification? {
+            if let Some(id) = parse_light_id(&arg.base_topic, &publish.topic) {
+                set_light(&id, &publish.payload);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Starts the bridge-state poller, once. It must not live inside `run`'s reconnect loop, since
+/// that would spawn a new poller (and leak the old one's thread) on every reconnect of the
+/// command connection. Instead, the poller keeps its own connection alive with its own retry
+/// loop, mirroring the one in `mqtt`.
+fn spawn_poller(arg: &Mqtt) {
+    let host = arg.host.clone();
+    let port = arg.port;
+    let base_topic = arg.base_topic.clone();
+    let interval = Duration::from_secs(arg.poll_interval);
+    thread::spawn(move || loop {
+        if let Err(e) = run_poller(&host, port, &base_topic, interval) {
+            eprintln!(
+                "Lost connection to the MQTT broker while polling, reconnecting in 5 seconds: {}",
+                e
+            );
+            thread::sleep(Duration::from_secs(5));
+        }
+    });
+}
+
+fn run_poller(
+    host: &str,
+    port: u16,
+    base_topic: &str,
+    interval: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut options = MqttOptions::new("huectl-rs-poll", host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut connection) = Client::new(options, 10);
+
+    let disconnected = Arc::new(AtomicBool::new(false));
+    let driver_disconnected = Arc::clone(&disconnected);
+    thread::spawn(move || {
+        for notification in connection.iter() {
+            if notification.is_err() {
+                break;
+            }
+        }
+        driver_disconnected.store(true, Ordering::SeqCst);
+    });
+
+    poll_lights(client, base_topic, interval, &disconnected)
+}
+
+fn parse_light_id(base_topic: &str, topic: &str) -> Option<String> {
+    let prefix = format!("{}/light/", base_topic);
+    topic
+        .strip_prefix(&prefix)?
+        .strip_suffix("/set")
+        .map(str::to_owned)
+}
+
+fn set_light(id: &str, payload: &[u8]) {
+    let command: light::LightCommand = match serde_json::from_slice(payload) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to parse command for light '{}': {}", id, e);
+            return;
+        }
+    };
+    let mut arg = match light::Set::try_from(command) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to parse command for light '{}': {}", id, e);
+            return;
+        }
+    };
+    arg.id = id.to_owned();
+    let bridge = util::get_bridge();
+    let state_modifier = arg.to_state_modifier();
+    if !state_modifier.is_empty() {
+        if let Err(e) = bridge.set_light_state(&arg.id, &state_modifier) {
+            eprintln!("Failed to set state of light '{}': {}", id, e);
+        }
+    }
+    let attribute_modifier = arg.to_attribute_modifier();
+    if !attribute_modifier.is_empty() {
+        if let Err(e) = bridge.set_light_attribute(&arg.id, &attribute_modifier) {
+            eprintln!("Failed to set attributes of light '{}': {}", id, e);
+        }
+    }
+}
+
+fn poll_lights(
+    mut client: Client,
+    base_topic: &str,
+    interval: Duration,
+    disconnected: &AtomicBool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bridge = util::get_bridge();
+    let mut published_states: HashMap<String, String> = HashMap::new();
+    loop {
+        if disconnected.load(Ordering::SeqCst) {
+            return Err("the poller's connection to the broker was dropped".into());
+        }
+        match bridge.get_all_lights() {
+            Ok(lights) => {
+                for light in lights {
+                    let id = light.id.clone();
+                    let state = match serde_json::to_string(&OutputLight::from(light)) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            eprintln!("Failed to serialize state of light '{}': {}", id, e);
+                            continue;
+                        }
+                    };
+                    if published_states.get(&id) == Some(&state) {
+                        continue;
+                    }
+                    let topic = format!("{}/light/{}/state", base_topic, id);
+                    match client.try_publish(&topic, QoS::AtLeastOnce, true, state.clone()) {
+                        Ok(_) => {
+                            published_states.insert(id, state);
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to publish state of light '{}': {}", id, e);
+                            break;
+                        }
+                    };
+                }
+            }
+            Err(e) => eprintln!("Failed to get lights from the bridge: {}", e),
+        };
+        thread::sleep(interval);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::output::{Group as OutputGroup, Light as OutputLight, Sensor as OutputSensor};
+use crate::util;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fmt, thread};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct Watch {
+    /// Resource type to watch
+    #[structopt(case_insensitive = true, possible_values = Resource::variants())]
+    resource: Resource,
+    /// Interval in seconds between polls of the bridge
+    #[structopt(long, short, default_value = "1")]
+    interval: u64,
+    /// Only watches the resource with this identifier
+    #[structopt(long, short)]
+    filter: Option<String>,
+    /// Prints changes as line-delimited JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Resource {
+    Light,
+    Group,
+    Sensor,
+}
+
+impl Resource {
+    fn variants() -> &'static [&'static str] {
+        &["light", "group", "sensor"]
+    }
+}
+
+impl FromStr for Resource {
+    type Err = super::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "light" => Ok(Self::Light),
+            "group" => Ok(Self::Group),
+            "sensor" => Ok(Self::Sensor),
+            _ => Err(super::ParseError::new("Invalid resource type")),
+        }
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Light => "light",
+            Self::Group => "group",
+            Self::Sensor => "sensor",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub fn watch(arg: Watch) {
+    let bridge = util::get_bridge();
+    let mut previous: HashMap<String, Value> = HashMap::new();
+    loop {
+        let snapshot = match poll(&bridge, arg.resource, &arg.filter) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Failed to poll {}s: {}", arg.resource, e);
+                thread::sleep(Duration::from_secs(arg.interval));
+                continue;
+            }
+        };
+        for (id, value) in &snapshot {
+            if let Some(previous_value) = previous.get(id) {
+                let changes = diff(previous_value, value);
+                if !changes.is_empty() {
+                    report(&arg, id, &changes);
+                }
+            }
+        }
+        previous = snapshot;
+        thread::sleep(Duration::from_secs(arg.interval));
+    }
+}
+
+fn poll(
+    bridge: &huelib::Bridge,
+    resource: Resource,
+    filter: &Option<String>,
+) -> Result<HashMap<String, Value>, Box<dyn std::error::Error>> {
+    let mut snapshot = HashMap::new();
+    match resource {
+        Resource::Light => match filter {
+            Some(id) => {
+                let light = bridge.get_light(id)?;
+                snapshot.insert(id.clone(), serde_json::to_value(OutputLight::from(light))?);
+            }
+            None => {
+                for light in bridge.get_all_lights()? {
+                    let id = light.id.clone();
+                    snapshot.insert(id, serde_json::to_value(OutputLight::from(light))?);
+                }
+            }
+        },
+        Resource::Group => match filter {
+            Some(id) => {
+                let group = bridge.get_group(id)?;
+                snapshot.insert(id.clone(), serde_json::to_value(OutputGroup::from(group))?);
+            }
+            None => {
+                for group in bridge.get_all_groups()? {
+                    let id = group.id.clone();
+                    snapshot.insert(id, serde_json::to_value(OutputGroup::from(group))?);
+                }
+            }
+        },
+        Resource::Sensor => match filter {
+            Some(id) => {
+                let sensor = bridge.get_sensor(id)?;
+                snapshot.insert(id.clone(), serde_json::to_value(OutputSensor::from(sensor))?);
+            }
+            None => {
+                for sensor in bridge.get_all_sensors()? {
+                    let id = sensor.id.clone();
+                    snapshot.insert(id, serde_json::to_value(OutputSensor::from(sensor))?);
+                }
+            }
+        },
+    };
+    Ok(snapshot)
+}
+
+/// Compares the top-level fields of two resource snapshots, returning the fields that changed.
+fn diff(old: &Value, new: &Value) -> serde_json::Map<String, Value> {
+    let mut changes = serde_json::Map::new();
+    if let (Value::Object(old_fields), Value::Object(new_fields)) = (old, new) {
+        for (key, new_value) in new_fields {
+            let old_value = old_fields.get(key);
+            if old_value != Some(new_value) {
+                changes.insert(
+                    key.clone(),
+                    serde_json::json!({"from": old_value, "to": new_value}),
+                );
+            }
+        }
+        for (key, old_value) in old_fields {
+            if !new_fields.contains_key(key) {
+                changes.insert(
+                    key.clone(),
+                    serde_json::json!({"from": old_value, "to": Value::Null}),
+                );
+            }
+        }
+    }
+    changes
+}
+
+fn report(arg: &Watch, id: &str, changes: &serde_json::Map<String, Value>) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or(0);
+    if arg.json {
+        let line = serde_json::json!({
+            "timestamp": timestamp,
+            "resource": arg.resource.to_string(),
+            "id": id,
+            "changes": changes,
+        });
+        println!("{}", line);
+    } else {
+        for (key, change) in changes {
+            println!(
+                "[{}] {} {}: {} -> {}",
+                timestamp, id, key, change["from"], change["to"]
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff_reports_no_changes_for_equal_objects() {
+        let value = json!({"on": true, "brightness": 100});
+        assert!(diff(&value, &value).is_empty());
+    }
+
+    #[test]
+    fn diff_reports_a_single_changed_field() {
+        let old = json!({"on": true, "brightness": 100});
+        let new = json!({"on": true, "brightness": 50});
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes["brightness"], json!({"from": 100, "to": 50}));
+    }
+
+    #[test]
+    fn diff_reports_multiple_changed_fields() {
+        let old = json!({"on": true, "brightness": 100, "hue": 1000});
+        let new = json!({"on": false, "brightness": 50, "hue": 1000});
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes["on"], json!({"from": true, "to": false}));
+        assert_eq!(changes["brightness"], json!({"from": 100, "to": 50}));
+    }
+
+    #[test]
+    fn diff_ignores_non_object_values_without_panicking() {
+        assert!(diff(&Value::Null, &Value::Null).is_empty());
+        assert!(diff(&Value::Null, &json!({"on": true})).is_empty());
+        assert!(diff(&json!({"on": true}), &Value::Null).is_empty());
+    }
+}
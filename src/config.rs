@@ -0,0 +1,17 @@
+/// Environment variable containing the IP address of the bridge.
+pub const VAR_BRIDGE_IP: &str = "HUECTL_BRIDGE_IP";
+/// Environment variable containing the username used to authenticate with the bridge.
+pub const VAR_BRIDGE_USERNAME: &str = "HUECTL_BRIDGE_USERNAME";
+
+/// Prints an error message to stderr and exits the process with a non-zero status code.
+#[macro_export]
+macro_rules! exit {
+    ($msg:expr) => {{
+        eprintln!("{}", $msg);
+        std::process::exit(1);
+    }};
+    ($msg:expr, $err:expr) => {{
+        eprintln!("{}: {}", $msg, $err);
+        std::process::exit(1);
+    }};
+}
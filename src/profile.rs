@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static SELECTED: OnceLock<Option<String>> = OnceLock::new();
+
+/// Credentials of a single named bridge, as stored in the config file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Profile {
+    pub ip_address: IpAddr,
+    pub username: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct File {
+    #[serde(default)]
+    profile: HashMap<String, Profile>,
+}
+
+fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|v| v.join("huectl").join("config.toml"))
+}
+
+fn load() -> File {
+    let path = match path() {
+        Some(v) => v,
+        None => return File::default(),
+    };
+    match fs::read_to_string(&path) {
+        Ok(v) => match toml::from_str(&v) {
+            Ok(file) => file,
+            Err(e) => exit!("Failed to parse the config file", e),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => File::default(),
+        Err(e) => exit!("Failed to read the config file", e),
+    }
+}
+
+/// Selects the profile to use for the rest of the process. Called once during startup with the
+/// value of the global `--profile` flag.
+pub fn select(name: Option<String>) {
+    let _ = SELECTED.set(name);
+}
+
+/// The result of resolving the profile selected with `--profile`.
+pub enum Active {
+    /// No `--profile` flag was given.
+    Unselected,
+    /// `--profile <name>` was given and a profile exists under that name.
+    Found(Profile),
+    /// `--profile <name>` was given but no profile exists under that name.
+    NotFound(String),
+}
+
+/// Returns the bridge profile selected with `--profile`, if any.
+pub fn active() -> Active {
+    let name = match SELECTED.get().and_then(|v| v.as_ref()) {
+        Some(v) => v,
+        None => return Active::Unselected,
+    };
+    match load().profile.get(name).cloned() {
+        Some(v) => Active::Found(v),
+        None => Active::NotFound(name.clone()),
+    }
+}
+
+/// Writes the given profile into the config file under `name`, creating the file if necessary.
+pub fn save(name: &str, profile: Profile) {
+    let path = match path() {
+        Some(v) => v,
+        None => exit!("Could not determine the location of the config file"),
+    };
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            exit!("Failed to create the config directory", e);
+        }
+    }
+    let mut file = load();
+    file.profile.insert(name.to_owned(), profile);
+    let content = match toml::to_string_pretty(&file) {
+        Ok(v) => v,
+        Err(e) => exit!("Failed to serialize the config file", e),
+    };
+    if let Err(e) = fs::write(&path, content) {
+        exit!("Failed to write the config file", e);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::config;
+use crate::profile;
+
+/// Builds a bridge from the `HUECTL_BRIDGE_IP`/`HUECTL_BRIDGE_USERNAME` environment variables,
+/// falling back to the profile selected with `--profile` when either is unset.
+pub fn get_bridge() -> huelib::Bridge {
+    let (ip_address, username) = match (
+        std::env::var(config::VAR_BRIDGE_IP),
+        std::env::var(config::VAR_BRIDGE_USERNAME),
+    ) {
+        (Ok(ip_address), Ok(username)) => (ip_address, username),
+        _ => match profile::active() {
+            profile::Active::Found(v) => (v.ip_address.to_string(), v.username),
+            profile::Active::Unselected => exit!(format!(
+                "Environment variables '{}' and '{}' are not set and no bridge profile is selected",
+                config::VAR_BRIDGE_IP,
+                config::VAR_BRIDGE_USERNAME
+            )),
+            profile::Active::NotFound(name) => {
+                exit!(format!("No bridge profile named '{}' was found", name))
+            }
+        },
+    };
+    let ip_address = match ip_address.parse() {
+        Ok(v) => v,
+        Err(e) => exit!(format!("Invalid IP address '{}'", ip_address), e),
+    };
+    huelib::Bridge::new(ip_address, username)
+}
+
+pub fn print_err<E: std::fmt::Display>(msg: &str, err: E) -> ! {
+    exit!(msg, err)
+}